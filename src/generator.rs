@@ -1,35 +1,208 @@
 use crate::parser::*;
+use std::collections::HashMap;
+
+/// Maps a variable name to its byte offset below `rbp` (i.e. its slot is `[rbp - offset]`).
+type StackSlots = HashMap<String, usize>;
 
 /// Responsible for converting a parsed syntax tree into x86-64 assembly language.
 pub struct Generator {
-    root: NodeExit,
+    program: NodeProgram,
 }
 
 impl Generator {
-    /// Constructs a new `Generator` from a provided root node.
+    /// Constructs a new `Generator` from a provided program.
     ///
     /// # Parameters
     ///
-    /// * `root`: The root node of the syntax tree, representing an exit statement.
+    /// * `program`: The root of the syntax tree, holding every function the source defines.
     ///
     /// # Returns
     ///
     /// A new `Generator` instance.
-    pub fn new(root: NodeExit) -> Self {
-        Self { root }
+    pub fn new(program: NodeProgram) -> Self {
+        Self { program }
     }
 
     /// Generates the assembly code from the provided syntax tree.
     ///
-    /// The generated code will invoke a system exit call with a status code from the root node.
+    /// Emits one label per function, with `_start` calling `main` and exiting with its
+    /// return value.
     ///
     /// # Returns
     ///
     /// A string containing the generated x86-64 assembly code.
     pub fn generate(&self) -> String {
-        format!(
-            "global _start\n_start:\n    mov rax, 60\n    mov rdi, {} \n    syscall\n",
-            self.root.expr.int_value
-        )
+        let mut asm = String::from("global _start\n_start:\n    call main\n    mov rdi, rax\n    mov rax, 60\n    syscall\n");
+        for function in &self.program.functions {
+            Self::gen_function(function, &mut asm);
+        }
+        asm
+    }
+
+    /// Emits a function label: prologue, body, and epilogue.
+    ///
+    /// Every variable bound by a `let` in the body gets its own stack slot, assigned in order
+    /// of first appearance (a later `let` of the same name reuses that slot); the prologue
+    /// reserves enough space for all of them up front.
+    fn gen_function(function: &NodeFunction, asm: &mut String) {
+        let slots = Self::assign_slots(function);
+        let frame_size = slots.len() * 8;
+        let epilogue_label = format!("{}_epilogue", function.name);
+
+        asm.push_str(&format!("{}:\n", function.name));
+        asm.push_str("    push rbp\n    mov rbp, rsp\n");
+        if frame_size > 0 {
+            asm.push_str(&format!("    sub rsp, {}\n", frame_size));
+        }
+
+        for stmt in &function.body {
+            Self::gen_stmt(stmt, &slots, &epilogue_label, asm);
+        }
+
+        asm.push_str(&format!("{}:\n", epilogue_label));
+        asm.push_str("    mov rsp, rbp\n    pop rbp\n    ret\n");
+    }
+
+    /// Looks up the stack offset for `name`, or panics with a message pointing at the
+    /// underlying bug.
+    ///
+    /// Every variable reaching codegen must have been scoped to its function and checked
+    /// against `declared_vars` by the parser, so a miss here means the parser let an
+    /// out-of-scope reference through rather than anything a user's source can trigger.
+    fn slot_offset(slots: &StackSlots, name: &str) -> usize {
+        *slots.get(name).unwrap_or_else(|| {
+            panic!(
+                "internal error: variable `{}` has no stack slot; the parser should have rejected it as undefined",
+                name
+            )
+        })
+    }
+
+    /// Walks a function's `let` statements in order, giving each distinct variable name the
+    /// next 8-byte slot below `rbp`.
+    fn assign_slots(function: &NodeFunction) -> StackSlots {
+        let mut slots = StackSlots::new();
+        for stmt in &function.body {
+            let NodeStmt::Let { name, .. } = stmt else {
+                continue;
+            };
+            if !slots.contains_key(name) {
+                let offset = (slots.len() + 1) * 8;
+                slots.insert(name.clone(), offset);
+            }
+        }
+        slots
+    }
+
+    /// Emits instructions for a single statement in a function body.
+    ///
+    /// `epilogue_label` is where `return` jumps to: it must run the same `mov rsp, rbp; pop
+    /// rbp; ret` sequence as falling off the end of the function, not just leave its value in
+    /// `rax` and fall through to whatever statement happens to follow.
+    fn gen_stmt(stmt: &NodeStmt, slots: &StackSlots, epilogue_label: &str, asm: &mut String) {
+        match stmt {
+            NodeStmt::Let { name, expr } => {
+                Self::gen_expr(expr, slots, asm);
+                let offset = Self::slot_offset(slots, name);
+                asm.push_str(&format!("    mov [rbp - {}], rax\n", offset));
+            }
+            NodeStmt::Return(expr) => {
+                Self::gen_expr(expr, slots, asm);
+                asm.push_str(&format!("    jmp {}\n", epilogue_label));
+            }
+            NodeStmt::Exit(expr) => {
+                Self::gen_expr(expr, slots, asm);
+                asm.push_str("    mov rdi, rax\n    mov rax, 60\n    syscall\n");
+            }
+            NodeStmt::Call(name) => {
+                asm.push_str(&format!("    call {}\n", name));
+            }
+        }
+    }
+
+    /// Emits instructions that evaluate `expr` and leave the result in `rax`.
+    ///
+    /// Binary operations spill the left-hand side onto the stack while the right-hand side
+    /// is evaluated, since both operands need a register once the operator is applied.
+    fn gen_expr(expr: &NodeExpr, slots: &StackSlots, asm: &mut String) {
+        match expr {
+            NodeExpr::IntLit(value) => {
+                asm.push_str(&format!("    mov rax, {}\n", value));
+            }
+            NodeExpr::Var(name) => {
+                let offset = Self::slot_offset(slots, name);
+                asm.push_str(&format!("    mov rax, [rbp - {}]\n", offset));
+            }
+            NodeExpr::BinOp { op, lhs, rhs } => {
+                Self::gen_expr(lhs, slots, asm);
+                asm.push_str("    push rax\n");
+                Self::gen_expr(rhs, slots, asm);
+                asm.push_str("    mov rbx, rax\n");
+                asm.push_str("    pop rax\n");
+                match op {
+                    BinOp::Add => asm.push_str("    add rax, rbx\n"),
+                    BinOp::Sub => asm.push_str("    sub rax, rbx\n"),
+                    BinOp::Mul => asm.push_str("    imul rax, rbx\n"),
+                    BinOp::Div => {
+                        asm.push_str("    cqo\n");
+                        asm.push_str("    idiv rbx\n");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    /// Tokenizes, parses, and generates assembly for `src`, without invoking `nasm`/`ld`.
+    fn generate(src: &str) -> String {
+        let tokens = Tokenizer::new(src.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().expect("expected parse to succeed");
+        Generator::new(program).generate()
+    }
+
+    #[test]
+    fn variables_get_slots_in_order_of_first_appearance() {
+        let asm = generate("fn main() { let x = 2 + 3; let y = x * x; exit(y); }");
+
+        assert!(asm.contains("    sub rsp, 16\n"));
+        assert!(asm.contains("    mov [rbp - 8], rax\n"));
+        assert!(asm.contains("    mov [rbp - 16], rax\n"));
+        assert!(asm.contains("    mov rax, [rbp - 8]\n"));
+        assert!(asm.contains("    mov rax, [rbp - 16]\n"));
+    }
+
+    #[test]
+    fn binary_op_spills_lhs_and_emits_the_matching_instruction() {
+        let asm = generate("fn main() { exit(2 + 3); }");
+
+        assert!(asm.contains("    mov rax, 2\n    push rax\n    mov rax, 3\n    mov rbx, rax\n    pop rax\n    add rax, rbx\n"));
+    }
+
+    #[test]
+    fn function_with_no_variables_reserves_no_stack_space() {
+        let asm = generate("fn main() { exit(0); }");
+
+        assert!(!asm.contains("sub rsp"));
+    }
+
+    #[test]
+    fn return_jumps_to_the_function_epilogue_instead_of_falling_through() {
+        let asm = generate("fn main() { return 1; }");
+
+        assert!(asm.contains("    jmp main_epilogue\n"));
+        assert!(asm.contains("main_epilogue:\n    mov rsp, rbp\n    pop rbp\n    ret\n"));
+    }
+
+    #[test]
+    fn call_statement_emits_a_call_instruction() {
+        let asm = generate("fn main() { helper(); exit(0); } fn helper() { return 1; }");
+
+        assert!(asm.contains("    call helper\n"));
     }
 }
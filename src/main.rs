@@ -34,13 +34,20 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut parser = Parser::new(tokens);
 
-    let tree = parser.parse();
-
-    if tree.is_none() {
-        panic!("No exit statement found");
+    let tree = match parser.parse() {
+        Ok(tree) => tree,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    if !tree.functions.iter().any(|function| function.name == "main") {
+        eprintln!("error: No 'main' function found");
+        std::process::exit(1);
     }
 
-    let generator = Generator::new(tree.unwrap());
+    let generator = Generator::new(tree);
 
     let out_path = Path::new(file_path).with_extension("asm");
     fs::write(&out_path, generator.generate())?;
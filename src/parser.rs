@@ -3,35 +3,95 @@
 //! The parser module provides structures and methods to transform a sequence of tokens into
 //! more abstract representations like expressions or other nodes.
 
+use crate::tokenizer::Span;
 use crate::Token;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
 
-/// Represents an expression node that contains an integer value.
+/// Represents a binary operator that can appear inside an expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinOp {
+    /// The `+` operator.
+    Add,
+    /// The `-` operator.
+    Sub,
+    /// The `*` operator.
+    Mul,
+    /// The `/` operator.
+    Div,
+}
+
+/// Represents an expression node in the abstract syntax tree.
 ///
-/// The `NodeExpr` struct is used to represent literal integer values in the abstract syntax tree.
+/// An expression is either a literal integer, a reference to a variable, or a binary
+/// operation combining two sub-expressions.
 ///
 /// # Examples
 ///
 /// ```
-/// let expr = NodeExpr { int_value: 5 };
+/// let expr = NodeExpr::IntLit(5);
 /// ```
-pub struct NodeExpr {
-    pub int_value: i64,
+pub enum NodeExpr {
+    /// A literal integer value, e.g. `5`.
+    IntLit(i64),
+    /// A reference to a variable by name, e.g. `x`.
+    Var(String),
+    /// A binary operation combining two sub-expressions, e.g. `1 + 2`.
+    BinOp {
+        op: BinOp,
+        lhs: Box<NodeExpr>,
+        rhs: Box<NodeExpr>,
+    },
 }
 
-/// Represents an exit node that contains an expression.
-///
-/// The `NodeExit` struct is used to represent exit nodes in the abstract syntax tree.
+/// Represents a single statement inside a function body.
+pub enum NodeStmt {
+    /// A `let name = value;` binding.
+    Let { name: String, expr: NodeExpr },
+    /// A `return expr;` statement.
+    Return(NodeExpr),
+    /// An `exit(expr);` statement, terminating the process with `expr` as the status code.
+    Exit(NodeExpr),
+    /// A call to another function by name, e.g. `helper();`.
+    Call(String),
+}
+
+/// Represents a function definition: a name and the statements in its body.
 ///
 /// # Examples
 ///
 /// ```
-/// let exit = NodeExit { expr: NodeExpr { int_value: 5 } };
+/// let function = NodeFunction { name: "main".to_string(), body: vec![] };
 /// ```
-pub struct NodeExit {
-    pub expr: NodeExpr,
+pub struct NodeFunction {
+    pub name: String,
+    pub body: Vec<NodeStmt>,
+}
+
+/// Represents a whole parsed source file: the set of functions it defines.
+pub struct NodeProgram {
+    pub functions: Vec<NodeFunction>,
+}
+
+/// An error produced while parsing, carrying the source location that caused it.
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}:{}",
+            self.message, self.span.line, self.span.col
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 /// `Parser` is responsible for parsing a list of tokens and constructing the appropriate node structures.
 ///
 /// The parser uses methods like `peek` and `consume` to iterate through the list of tokens and uses
@@ -40,14 +100,16 @@ pub struct NodeExit {
 /// # Examples
 ///
 /// ```
-/// let tokens = // ... generate or provide list of tokens
+/// let tokens = // ... generate or provide list of tokens, each paired with its `Span`
 /// let mut parser = Parser::new(tokens);
 /// let ast = parser.parse();
 /// ```
 pub(crate) struct Parser {
-    tokens: Vec<Token>, // List of tokens to be parsed
-    index: usize,       // Index pointing to the current token
-    symbol_table: HashMap<String, i64>,
+    tokens: Vec<(Token, Span)>, // List of tokens to be parsed, each with its source position
+    index: usize,               // Index pointing to the current token
+    declared_vars: HashSet<String>,
+    declared_functions: HashSet<String>,
+    defined_functions: HashSet<String>,
 }
 
 impl Parser {
@@ -55,15 +117,33 @@ impl Parser {
     ///
     /// # Parameters
     ///
-    /// * `tokens`: A vector of tokens to be parsed.
-    pub fn new(tokens: Vec<Token>) -> Parser {
+    /// * `tokens`: A vector of tokens, each paired with its source `Span`, to be parsed.
+    pub fn new(tokens: Vec<(Token, Span)>) -> Parser {
         Self {
             tokens,
             index: 0,
-            symbol_table: HashMap::new(),
+            declared_vars: HashSet::new(),
+            declared_functions: HashSet::new(),
+            defined_functions: HashSet::new(),
         }
     }
 
+    /// Scans every `fn <name>` in the token stream up front, so a call can be validated
+    /// against the full set of functions the program defines regardless of whether the
+    /// callee appears before or after the call site.
+    fn collect_function_names(&self) -> HashSet<String> {
+        let mut names = HashSet::new();
+        for i in 0..self.tokens.len() {
+            if !matches!(self.tokens[i].0, Token::Fn) {
+                continue;
+            }
+            if let Some((Token::Identifier(name), _)) = self.tokens.get(i + 1) {
+                names.insert(name.clone());
+            }
+        }
+        names
+    }
+
     /// Returns a token `ahead` positions from the current index.
     ///
     /// This method provides a way to look ahead in the token list without consuming any tokens.
@@ -72,11 +152,7 @@ impl Parser {
     ///
     /// * `ahead`: Number of positions ahead of the current token.
     fn peek(&self, ahead: usize) -> Option<Token> {
-        if self.index + ahead >= self.tokens.len() {
-            None
-        } else {
-            Some(self.tokens[self.index + ahead].clone())
-        }
+        self.tokens.get(self.index + ahead).map(|(token, _)| token.clone())
     }
 
     /// Utility function to peek at the current token without advancing the index.
@@ -84,107 +160,427 @@ impl Parser {
         self.peek(0)
     }
 
+    /// Returns the span of the current token, or the span of the last token in the stream
+    /// (treated as the end-of-file position) if there is no current token.
+    fn current_span(&self) -> Span {
+        self.tokens
+            .get(self.index)
+            .or_else(|| self.tokens.last())
+            .map(|(_, span)| *span)
+            .unwrap_or(Span { line: 1, col: 1 })
+    }
+
+    /// Returns the span of the token that was just consumed.
+    fn previous_span(&self) -> Span {
+        self.tokens[self.index - 1].1
+    }
+
+    /// Builds a `ParseError` at the given span.
+    fn error(&self, span: Span, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            span,
+        }
+    }
+
     /// Consumes the current token and advances to the next one.
     ///
     /// This method allows the parser to advance through the list of tokens.
     ///
     /// # Returns
     ///
-    /// The token that was just consumed.
-    fn consume(&mut self) -> Token {
+    /// The token that was just consumed, or a `ParseError` if the token stream is exhausted
+    /// (e.g. a source that ends mid-construct, such as a missing closing `}`, `)`, or `;`).
+    fn consume(&mut self) -> Result<Token, ParseError> {
+        if self.index >= self.tokens.len() {
+            return Err(self.error(self.current_span(), "Unexpected end of input."));
+        }
         self.index += 1;
-        self.tokens[self.index - 1].clone()
+        Ok(self.tokens[self.index - 1].0.clone())
     }
 
-    fn parse_assignment(&mut self) {
+    /// Parses a `let name = expr;` statement, consuming its trailing semicolon.
+    ///
+    /// The variable's value is no longer folded at parse time; `name` is only recorded as
+    /// declared so later reads can be resolved to a `NodeExpr::Var`, and the `Generator`
+    /// gives it an actual stack slot.
+    fn parse_assignment(&mut self) -> Result<NodeStmt, ParseError> {
         // Since we already know the current token is `Let`, we don't need to check it again.
         // So, just consume it.
-        self.consume();
+        self.consume()?;
 
         // Expect and consume identifier for the variable name
-        let var_name = match self.consume() {
+        let var_name = match self.consume()? {
             Token::Identifier(name) => name,
-            _ => panic!("Expected identifier for variable name."),
+            _ => return Err(self.error(self.previous_span(), "Expected identifier for variable name.")),
         };
 
         // Expect and consume the equals sign
-        match self.consume() {
+        match self.consume()? {
             Token::Equals => (),
-            _ => panic!("Expected equals sign after identifier."),
+            _ => return Err(self.error(self.previous_span(), "Expected equals sign after identifier.")),
         }
 
-        // Expect and consume the integer literal (value assigned to the variable)
-        let value = match self.consume() {
-            Token::IntLiteral(val) => val,
-            _ => panic!("Expected an integer after equals sign."),
-        };
+        let expr = self.parse_expression(0)?;
 
-        // Insert the variable and its value into the symbol table
-        self.symbol_table.insert(var_name.to_string(), value);
+        self.expect_semicolon()?;
+
+        self.declared_vars.insert(var_name.clone());
+
+        Ok(NodeStmt::Let {
+            name: var_name,
+            expr,
+        })
     }
 
-    /// Parses the tokens into a `NodeExit` structure.
+    /// Consumes a `;`, or reports a `ParseError` pointing at the offending token.
+    fn expect_semicolon(&mut self) -> Result<(), ParseError> {
+        match self.consume()? {
+            Token::Semicolon => Ok(()),
+            _ => Err(self.error(self.previous_span(), "Expected ';' after statement.")),
+        }
+    }
+
+    /// Parses the tokens into a `NodeProgram` made up of function definitions.
     ///
     /// # Returns
     ///
-    /// An `Option` containing a `NodeExit` structure if parsing is successful, or `None` if not.
-    pub fn parse(&mut self) -> Option<NodeExit> {
-        let mut exit_node = None;
+    /// The parsed `NodeProgram`, or a `ParseError` pointing at the offending token if the input
+    /// is malformed.
+    pub fn parse(&mut self) -> Result<NodeProgram, ParseError> {
+        self.declared_functions = self.collect_function_names();
+        let mut functions = Vec::new();
+
+        while self.peek_current().is_some() {
+            match self.peek_current() {
+                Some(Token::Fn) => functions.push(self.parse_function()?),
+                _ => return Err(self.error(self.current_span(), "Expected a function definition.")),
+            }
+        }
+
+        Ok(NodeProgram { functions })
+    }
+
+    /// Parses a `fn name() { ... }` definition.
+    ///
+    /// Variables are scoped to their enclosing function, so `declared_vars` is cleared before
+    /// parsing the body; a `let` in one function must not make its name resolvable in another.
+    fn parse_function(&mut self) -> Result<NodeFunction, ParseError> {
+        self.consume()?; // 'fn'
+        self.declared_vars.clear();
+
+        let name = match self.consume()? {
+            Token::Identifier(name) => name,
+            _ => return Err(self.error(self.previous_span(), "Expected a function name after 'fn'.")),
+        };
+        let name_span = self.previous_span();
+
+        if !self.defined_functions.insert(name.clone()) {
+            return Err(self.error(name_span, format!("Duplicate function definition: {}", name)));
+        }
+
+        match self.consume()? {
+            Token::OpenParen => (),
+            _ => return Err(self.error(self.previous_span(), "Expected '(' after function name.")),
+        }
+        match self.consume()? {
+            Token::CloseParen => (),
+            _ => return Err(self.error(self.previous_span(), "Expected ')' after '('.")),
+        }
+        match self.consume()? {
+            Token::OpenBrace => (),
+            _ => return Err(self.error(self.previous_span(), "Expected '{' to start function body.")),
+        }
+
+        let mut body = Vec::new();
+        while !matches!(self.peek_current(), Some(Token::CloseBrace) | None) {
+            let stmt = self.parse_statement()?;
+            let is_return = matches!(stmt, NodeStmt::Return(_));
+            body.push(stmt);
+
+            if is_return && !matches!(self.peek_current(), Some(Token::CloseBrace) | None) {
+                return Err(self.error(self.current_span(), "Unreachable statement after 'return'."));
+            }
+        }
+
+        match self.consume()? {
+            Token::CloseBrace => (),
+            _ => return Err(self.error(self.previous_span(), "Expected '}' to close function body.")),
+        }
+
+        Ok(NodeFunction { name, body })
+    }
+
+    /// Parses a single statement inside a function body.
+    fn parse_statement(&mut self) -> Result<NodeStmt, ParseError> {
+        match self.peek_current() {
+            Some(Token::Let) => self.parse_assignment(),
+            Some(Token::Return) => {
+                self.consume()?;
+                let expr = self.parse_expression(0)?;
+                self.expect_semicolon()?;
+                Ok(NodeStmt::Return(expr))
+            }
+            Some(Token::Exit) => {
+                self.consume()?;
+                match self.consume()? {
+                    Token::OpenParen => (),
+                    _ => return Err(self.error(self.previous_span(), "Expected an open parenthesis after 'exit'.")),
+                }
+
+                let expr = self.parse_expression(0)?;
 
-        while let Some(token) = self.peek_current() {
-            match token {
-                Token::Let => {
-                    self.parse_assignment();
+                match self.consume()? {
+                    Token::CloseParen => (),
+                    _ => return Err(self.error(self.previous_span(), "Expected a close parenthesis after expression.")),
                 }
-                Token::Exit => {
-                    self.consume();
-                    match self.consume() {
-                        Token::OpenParen => (),
-                        _ => panic!("Expected an open parenthesis after 'exit'."),
-                    }
-
-                    let expr = self.parse_expression();
-
-                    match self.consume() {
-                        Token::CloseParen => (),
-                        _ => panic!("Expected a close parenthesis after expression."),
-                    }
-
-                    exit_node = Some(NodeExit {
-                        expr: expr.unwrap(),
-                    }); // You might want to handle this unwrap more gracefully.
+                self.expect_semicolon()?;
+
+                Ok(NodeStmt::Exit(expr))
+            }
+            Some(Token::Identifier(name)) if self.peek(1) == Some(Token::OpenParen) => {
+                let name_span = self.current_span();
+                self.consume()?;
+                self.consume()?; // '('
+                match self.consume()? {
+                    Token::CloseParen => (),
+                    _ => return Err(self.error(self.previous_span(), "Expected ')' after '(' in function call.")),
                 }
-                _ => {
-                    self.consume();
+                self.expect_semicolon()?;
+
+                if !self.declared_functions.contains(&name) {
+                    return Err(self.error(name_span, format!("Call to undefined function: {}", name)));
                 }
+
+                Ok(NodeStmt::Call(name))
             }
+            _ => Err(self.error(self.current_span(), "Expected a statement.")),
         }
+    }
 
-        exit_node.or(Some(NodeExit {
-            expr: NodeExpr { int_value: 0 },
-        }))
+    /// Returns the binding power of a binary operator as `(left, right)`.
+    ///
+    /// The left power determines whether the operator binds tighter than the enclosing
+    /// call's `min_bp`; the right power is fed back into the recursive call for the right
+    /// operand. Using `left + 1` as the right power gives left-associativity.
+    fn binding_power(op: BinOp) -> (u8, u8) {
+        match op {
+            BinOp::Add | BinOp::Sub => (1, 2),
+            BinOp::Mul | BinOp::Div => (2, 3),
+        }
+    }
+
+    /// Maps the current token to a `BinOp`, if it is one, without consuming it.
+    fn peek_bin_op(&self) -> Option<BinOp> {
+        match self.peek_current()? {
+            Token::Plus => Some(BinOp::Add),
+            Token::Minus => Some(BinOp::Sub),
+            Token::Star => Some(BinOp::Mul),
+            Token::Slash => Some(BinOp::Div),
+            _ => None,
+        }
     }
 
-    /// Parses an expression from the tokens.
+    /// Parses an expression using precedence climbing (a.k.a. Pratt parsing).
+    ///
+    /// `min_bp` is the minimum binding power a binary operator must have to be consumed by
+    /// this call; recursive calls raise it to bind sub-expressions more tightly than their
+    /// surrounding context.
     ///
     /// # Returns
     ///
-    /// An `Option` containing a `NodeExpr` if parsing is successful, or `None` if not.
-    fn parse_expression(&mut self) -> Option<NodeExpr> {
+    /// The `NodeExpr` if parsing is successful, or a `ParseError` pointing at the offending
+    /// token otherwise.
+    fn parse_expression(&mut self, min_bp: u8) -> Result<NodeExpr, ParseError> {
+        let mut lhs = self.parse_primary()?;
+
+        while let Some(op) = self.peek_bin_op() {
+            let (left_bp, right_bp) = Self::binding_power(op);
+            if left_bp < min_bp {
+                break;
+            }
+
+            self.consume()?;
+            let rhs = self.parse_expression(right_bp)?;
+
+            lhs = NodeExpr::BinOp {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses a single primary expression: a literal, a variable, a parenthesized
+    /// sub-expression, or a unary-minus applied to one of those.
+    fn parse_primary(&mut self) -> Result<NodeExpr, ParseError> {
         match self.peek_current() {
+            Some(Token::Minus) => {
+                self.consume()?;
+                let operand = self.parse_primary()?;
+                Ok(NodeExpr::BinOp {
+                    op: BinOp::Sub,
+                    lhs: Box::new(NodeExpr::IntLit(0)),
+                    rhs: Box::new(operand),
+                })
+            }
             Some(Token::IntLiteral(int_value)) => {
-                self.consume();
-                Some(NodeExpr { int_value })
+                self.consume()?;
+                Ok(NodeExpr::IntLit(int_value))
             }
             Some(Token::Identifier(name)) => {
-                self.consume();
-                if let Some(value) = self.symbol_table.get(&name) {
-                    Some(NodeExpr { int_value: *value })
+                self.consume()?;
+                if self.declared_vars.contains(&name) {
+                    Ok(NodeExpr::Var(name))
                 } else {
-                    panic!("Undefined variable: {}", name);
+                    Err(self.error(self.previous_span(), format!("Undefined variable: {}", name)))
                 }
             }
-            _ => None,
+            Some(Token::OpenParen) => {
+                self.consume()?;
+                let expr = self.parse_expression(0)?;
+                match self.consume()? {
+                    Token::CloseParen => (),
+                    _ => return Err(self.error(self.previous_span(), "Expected a close parenthesis after expression.")),
+                }
+                Ok(expr)
+            }
+            _ => Err(self.error(self.current_span(), "Expected an expression.")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Tokenizer;
+
+    fn parse_ok(src: &str) -> NodeProgram {
+        let tokens = Tokenizer::new(src.to_string()).tokenize();
+        Parser::new(tokens).parse().expect("expected parse to succeed")
+    }
+
+    fn parse_err(src: &str) -> ParseError {
+        let tokens = Tokenizer::new(src.to_string()).tokenize();
+        match Parser::new(tokens).parse() {
+            Err(err) => err,
+            Ok(_) => panic!("expected parse to fail"),
+        }
+    }
+
+    /// Pulls the expression out of the first function's first statement, assuming it's an
+    /// `exit(...)`; panics with a message naming the assumption if it isn't.
+    fn first_exit_expr(program: &NodeProgram) -> &NodeExpr {
+        match &program.functions[0].body[0] {
+            NodeStmt::Exit(expr) => expr,
+            _ => panic!("expected the first statement to be an Exit"),
+        }
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        let program = parse_ok("fn main() { exit(1 + 2 * 3); }");
+        match first_exit_expr(&program) {
+            NodeExpr::BinOp { op: BinOp::Add, lhs, rhs } => {
+                assert!(matches!(**lhs, NodeExpr::IntLit(1)));
+                assert!(matches!(**rhs, NodeExpr::BinOp { op: BinOp::Mul, .. }));
+            }
+            _ => panic!("expected `1 + 2 * 3` to parse as Add(1, Mul(2, 3))"),
+        }
+    }
+
+    #[test]
+    fn division_is_left_associative() {
+        let program = parse_ok("fn main() { exit(8 / 4 / 2); }");
+        match first_exit_expr(&program) {
+            NodeExpr::BinOp { op: BinOp::Div, lhs, rhs } => {
+                assert!(matches!(**lhs, NodeExpr::BinOp { op: BinOp::Div, .. }));
+                assert!(matches!(**rhs, NodeExpr::IntLit(2)));
+            }
+            _ => panic!("expected `8 / 4 / 2` to parse as Div(Div(8, 4), 2)"),
+        }
+    }
+
+    #[test]
+    fn parens_override_precedence() {
+        let program = parse_ok("fn main() { exit((1 + 2) * 3); }");
+        match first_exit_expr(&program) {
+            NodeExpr::BinOp { op: BinOp::Mul, lhs, .. } => {
+                assert!(matches!(**lhs, NodeExpr::BinOp { op: BinOp::Add, .. }));
+            }
+            _ => panic!("expected `(1 + 2) * 3` to parse as Mul(Add(1, 2), 3)"),
+        }
+    }
+
+    #[test]
+    fn unary_minus_desugars_to_zero_minus_operand() {
+        let program = parse_ok("fn main() { exit(-5); }");
+        match first_exit_expr(&program) {
+            NodeExpr::BinOp { op: BinOp::Sub, lhs, rhs } => {
+                assert!(matches!(**lhs, NodeExpr::IntLit(0)));
+                assert!(matches!(**rhs, NodeExpr::IntLit(5)));
+            }
+            _ => panic!("expected `-5` to desugar to `0 - 5`"),
+        }
+    }
+
+    #[test]
+    fn parse_error_span_points_at_the_offending_token() {
+        let err = parse_err("fn main() {\n    let x 1;\n}");
+        assert_eq!(err.span.line, 2);
+        assert_eq!(err.message, "Expected equals sign after identifier.");
+    }
+
+    #[test]
+    fn undefined_variable_is_rejected() {
+        let err = parse_err("fn main() { exit(x); }");
+        assert_eq!(err.message, "Undefined variable: x");
+    }
+
+    #[test]
+    fn variables_do_not_leak_across_function_scopes() {
+        let err = parse_err("fn helper() { let x = 1; exit(x); } fn main() { exit(x); }");
+        assert_eq!(err.message, "Undefined variable: x");
+    }
+
+    #[test]
+    fn call_to_a_function_defined_later_is_allowed() {
+        parse_ok("fn main() { helper(); exit(0); } fn helper() { return 1; }");
+    }
+
+    #[test]
+    fn call_to_an_undefined_function_is_rejected() {
+        let err = parse_err("fn main() { missing(); exit(0); }");
+        assert_eq!(err.message, "Call to undefined function: missing");
+    }
+
+    #[test]
+    fn statement_after_return_is_rejected() {
+        let err = parse_err("fn f() { return 1; let x = 2; }");
+        assert_eq!(err.message, "Unreachable statement after 'return'.");
+    }
+
+    #[test]
+    fn duplicate_function_definition_is_rejected() {
+        let err = parse_err("fn main() { exit(0); } fn main() { exit(1); }");
+        assert_eq!(err.message, "Duplicate function definition: main");
+    }
+
+    #[test]
+    fn truncated_input_is_a_parse_error_not_a_panic() {
+        for src in [
+            "fn",
+            "fn main",
+            "fn main(",
+            "fn main()",
+            "fn main() {",
+            "fn main() { let x",
+            "fn main() { exit(1",
+        ] {
+            let err = parse_err(src);
+            assert_eq!(err.message, "Unexpected end of input.");
         }
     }
 }
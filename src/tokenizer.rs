@@ -1,3 +1,13 @@
+/// A 1-based line/column position in the source file.
+///
+/// Every token carries one of these so that parse errors can point back at the exact
+/// place in the source that caused them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
 /// Represents the types of tokens that can be produced by the lexer.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -17,6 +27,75 @@ pub enum Token {
     Equals,
     /// Represents variable names like 'x'
     Identifier(String),
+    /// Represents the '+' symbol.
+    Plus,
+    /// Represents the '-' symbol.
+    Minus,
+    /// Represents the '*' symbol.
+    Star,
+    /// Represents the '/' symbol.
+    Slash,
+    /// Represents the 'fn' keyword.
+    Fn,
+    /// Represents the 'return' keyword.
+    Return,
+    /// Represents the '{' symbol.
+    OpenBrace,
+    /// Represents the '}' symbol.
+    CloseBrace,
+}
+
+/// Walks a char buffer while tracking the current `(line, col)` position.
+///
+/// Keeping the position logic here, rather than scattered through `tokenize`, is what lets
+/// the lexer look ahead (e.g. to recognize a `//` comment) without losing track of spans.
+struct Cursor<'a> {
+    chars: &'a [char],
+    idx: usize,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(chars: &'a [char]) -> Self {
+        Self {
+            chars,
+            idx: 0,
+            line: 1,
+            col: 1,
+        }
+    }
+
+    /// Returns the current character without consuming it.
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).copied()
+    }
+
+    /// Returns the character `n` positions ahead of the current one, without consuming it.
+    fn peek_ahead(&self, n: usize) -> Option<char> {
+        self.chars.get(self.idx + n).copied()
+    }
+
+    /// The span of the character that `peek()` would currently return.
+    fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    /// Consumes the current character, updating line/col (bumping the line on `\n`).
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.idx += 1;
+        if ch == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(ch)
+    }
 }
 
 pub struct Tokenizer {
@@ -30,52 +109,118 @@ impl Tokenizer {
         }
     }
 
-    pub fn tokenize(&self) -> Vec<Token> {
+    pub fn tokenize(&self) -> Vec<(Token, Span)> {
         let mut tokens = Vec::new();
-        let mut current_token = String::new();
+        let mut cursor = Cursor::new(&self.src);
 
-        for ch in &self.src {
+        while let Some(ch) = cursor.peek() {
             match ch {
-                ' ' | ';' | '(' | ')' | '=' | '\n' => {
-                    if !current_token.is_empty() {
-                        if current_token == "exit" {
-                            tokens.push(Token::Exit);
-                        } else if current_token == "let" {
-                            tokens.push(Token::Let);
-                        } else if let Ok(value) = current_token.parse::<i64>() {
-                            tokens.push(Token::IntLiteral(value));
-                        } else {
-                            tokens.push(Token::Identifier(current_token.trim().to_string()));
+                ' ' | '\n' => {
+                    cursor.advance();
+                }
+                '/' if cursor.peek_ahead(1) == Some('/') => {
+                    while let Some(c) = cursor.peek() {
+                        if c == '\n' {
+                            break;
                         }
-                        current_token.clear();
+                        cursor.advance();
                     }
-
-                    match ch {
-                        ';' => tokens.push(Token::Semicolon),
-                        '(' => tokens.push(Token::OpenParen),
-                        ')' => tokens.push(Token::CloseParen),
-                        '=' => tokens.push(Token::Equals),
-                        _ => {}
+                }
+                ';' | '(' | ')' | '{' | '}' | '=' | '+' | '-' | '*' | '/' => {
+                    let span = cursor.span();
+                    cursor.advance();
+                    let token = match ch {
+                        ';' => Token::Semicolon,
+                        '(' => Token::OpenParen,
+                        ')' => Token::CloseParen,
+                        '{' => Token::OpenBrace,
+                        '}' => Token::CloseBrace,
+                        '=' => Token::Equals,
+                        '+' => Token::Plus,
+                        '-' => Token::Minus,
+                        '*' => Token::Star,
+                        '/' => Token::Slash,
+                        _ => unreachable!(),
+                    };
+                    tokens.push((token, span));
+                }
+                _ if ch.is_alphanumeric() || ch == '_' => {
+                    let span = cursor.span();
+                    let mut word = String::new();
+                    while let Some(c) = cursor.peek() {
+                        if c.is_alphanumeric() || c == '_' {
+                            word.push(c);
+                            cursor.advance();
+                        } else {
+                            break;
+                        }
                     }
+                    tokens.push((Self::classify_word(&word), span));
+                }
+                _ => {
+                    cursor.advance();
                 }
-                _ => current_token.push(*ch),
             }
         }
 
-        dbg!(&tokens);
-
-        if !current_token.is_empty() {
-            if current_token == "exit" {
-                tokens.push(Token::Exit);
-            } else if current_token == "let" {
-                tokens.push(Token::Let);
-            } else if let Ok(value) = current_token.parse::<i64>() {
-                tokens.push(Token::IntLiteral(value));
-            } else {
-                tokens.push(Token::Identifier(current_token.trim().to_string()));
-            }
+        tokens
+    }
+
+    /// Classifies a run of identifier/number characters into a keyword, integer literal, or
+    /// plain identifier. Shared by the single word-lexing branch in `tokenize` so keyword,
+    /// literal, and identifier handling stay in one place instead of being duplicated.
+    fn classify_word(word: &str) -> Token {
+        match word {
+            "exit" => Token::Exit,
+            "let" => Token::Let,
+            "fn" => Token::Fn,
+            "return" => Token::Return,
+            _ => match word.parse::<i64>() {
+                Ok(value) => Token::IntLiteral(value),
+                Err(_) => Token::Identifier(word.to_string()),
+            },
         }
+    }
+}
 
-        tokens
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_line_comments() {
+        let tokens = Tokenizer::new("1 // a comment\n+ 2".to_string()).tokenize();
+        let kinds: Vec<_> = tokens.into_iter().map(|(t, _)| t).collect();
+        assert_eq!(kinds, vec![Token::IntLiteral(1), Token::Plus, Token::IntLiteral(2)]);
+    }
+
+    #[test]
+    fn tracks_line_and_column_across_newlines() {
+        let tokens = Tokenizer::new("let x\n= 1;".to_string()).tokenize();
+        let equals_span = tokens.iter().find(|(t, _)| *t == Token::Equals).unwrap().1;
+        assert_eq!(equals_span, Span { line: 2, col: 1 });
+    }
+
+    #[test]
+    fn classifies_keywords_numbers_and_identifiers() {
+        let tokens = Tokenizer::new("fn return let exit x42 123".to_string()).tokenize();
+        let kinds: Vec<_> = tokens.into_iter().map(|(t, _)| t).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Token::Fn,
+                Token::Return,
+                Token::Let,
+                Token::Exit,
+                Token::Identifier("x42".to_string()),
+                Token::IntLiteral(123),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_run_of_digits_is_a_single_token() {
+        let tokens = Tokenizer::new("12345".to_string()).tokenize();
+        assert_eq!(tokens, vec![(Token::IntLiteral(12345), Span { line: 1, col: 1 })]);
     }
 }